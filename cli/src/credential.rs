@@ -1,14 +1,21 @@
 use std::io::{stdin, stdout, BufReader, BufWriter, Read};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::{Args, Subcommand};
 use didkit::{
     generate_proof, ContextLoader, LinkedDataProofOptions, ProofFormat, VerifiableCredential, JWK,
 };
+use serde_json::{json, Value};
 use tracing::warn;
 use didkit::ssi;
 
-use crate::{get_ssh_agent_sock, opts::ResolverOptions, KeyArg, ProofOptions};
+use crate::{
+    error::{self, report_and_exit, OutputFormat},
+    get_ssh_agent_sock,
+    oid4vci::{self, CredentialOfferArgs, CredentialRequestArgs},
+    opts::ResolverOptions,
+    sd_jwt, KeyArg, ProofOptions,
+};
 
 #[derive(Subcommand)]
 pub enum CredentialCmd {
@@ -20,6 +27,10 @@ pub enum CredentialCmd {
     Derive(CredentialDeriveArgs),
     /// Query Credential
     Query(CredentialQueryArgs),
+    /// Produce an OID4VCI credential offer for an already-issued credential
+    Offer(CredentialOfferArgs),
+    /// Handle a wallet's OID4VCI `/credential` request for an already-issued credential
+    Request(CredentialRequestArgs),
 }
 
 #[derive(Args)]
@@ -30,23 +41,36 @@ pub struct CredentialIssueArgs {
     proof_options: ProofOptions,
     #[clap(flatten)]
     resolver_options: ResolverOptions,
+    /// Claims in `credentialSubject` to selectively disclose, when issuing as SD-JWT
+    #[clap(long, num_args(0..))]
+    sd_claims: Vec<String>,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
 }
 
 #[derive(Args)]
 pub struct CredentialDeriveArgs {
-    /// Nonce provided by the verifier
+    /// Nonce provided by the verifier; required when deriving a BBS+ proof, unused when
+    /// selecting disclosures from an SD-JWT
     #[clap(short, long)]
-    proof_nonce: String,
-    /// Properties to include  
+    proof_nonce: Option<String>,
+    /// Properties to include
     #[clap(short, long, num_args(0..))]
     selectors: Vec<String>,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
 }
 
 #[derive(Args)]
 pub struct CredentialQueryArgs {
     /// Properties to include
     #[clap(short, long, num_args(0..))]
-    selectors: Vec<String>,    
+    selectors: Vec<String>,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
 }
 
 #[derive(Args)]
@@ -55,14 +79,52 @@ pub struct CredentialVerifyArgs {
     proof_options: ProofOptions,
     #[clap(flatten)]
     resolver_options: ResolverOptions,
+    /// N-quad statements disclosed by a prior `derive`, for verifying a BBS+ derived proof
+    #[clap(long, alias = "selectors", num_args(0..))]
+    disclosed: Vec<String>,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
 }
 
 pub async fn cli(cmd: CredentialCmd) -> Result<()> {
     match cmd {
-        CredentialCmd::Issue(cmd_issue) => issue(*cmd_issue).await?,
-        CredentialCmd::Verify(cmd_verify) => verify(cmd_verify).await?,
-        CredentialCmd::Derive(cmd_derive) => derive(cmd_derive).await?,
-        CredentialCmd::Query(cmd_query) => get_nquad_positions(cmd_query).await?,
+        CredentialCmd::Issue(cmd_issue) => {
+            let format = cmd_issue.format;
+            issue(*cmd_issue)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        CredentialCmd::Verify(cmd_verify) => {
+            let format = cmd_verify.format;
+            verify(cmd_verify)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        CredentialCmd::Derive(cmd_derive) => {
+            let format = cmd_derive.format;
+            derive(cmd_derive)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        CredentialCmd::Query(cmd_query) => {
+            let format = cmd_query.format;
+            get_nquad_positions(cmd_query)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        CredentialCmd::Offer(cmd_offer) => {
+            let format = cmd_offer.format;
+            oid4vci::offer(cmd_offer)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        CredentialCmd::Request(cmd_request) => {
+            let format = cmd_request.format;
+            oid4vci::request(cmd_request)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
     };
     Ok(())
 }
@@ -71,7 +133,8 @@ pub async fn issue(args: CredentialIssueArgs) -> Result<()> {
     let resolver = args.resolver_options.to_resolver();
     let mut context_loader = ContextLoader::default();
     let credential_reader = BufReader::new(stdin());
-    let mut credential: VerifiableCredential = serde_json::from_reader(credential_reader).unwrap();
+    let mut credential: VerifiableCredential = serde_json::from_reader(credential_reader)
+        .context("unable to parse credential JSON from stdin")?;
     let proof_format = args.proof_options.proof_format.clone();
     let jwk_opt: Option<JWK> = args.key.get_jwk_opt();
     let ssh_agent_sock_opt = if args.key.ssh_agent {
@@ -82,13 +145,23 @@ pub async fn issue(args: CredentialIssueArgs) -> Result<()> {
     let options = LinkedDataProofOptions::from(args.proof_options);
     match proof_format {
         ProofFormat::JWT => {
-            if ssh_agent_sock_opt.is_some() {
-                todo!("ssh-agent for JWT not implemented");
-            }
-            let jwt = credential
-                .generate_jwt(jwk_opt.as_ref(), &options, &resolver)
-                .await
-                .unwrap();
+            let jwt = match ssh_agent_sock_opt.as_deref() {
+                Some(ssh_agent_sock) => {
+                    credential
+                        .generate_jwt_with_ssh_agent(
+                            jwk_opt.as_ref(),
+                            &options,
+                            &resolver,
+                            ssh_agent_sock,
+                        )
+                        .await?
+                }
+                None => {
+                    credential
+                        .generate_jwt(jwk_opt.as_ref(), &options, &resolver)
+                        .await?
+                }
+            };
             print!("{jwt}");
         }
         ProofFormat::LDP => {
@@ -100,14 +173,32 @@ pub async fn issue(args: CredentialIssueArgs) -> Result<()> {
                 &mut context_loader,
                 ssh_agent_sock_opt.as_deref(),
             )
-            .await
-            .unwrap();
+            .await?;
             credential.add_proof(proof);
             let stdout_writer = BufWriter::new(stdout());
-            serde_json::to_writer(stdout_writer, &credential).unwrap();
+            serde_json::to_writer(stdout_writer, &credential)?;
+        }
+        ProofFormat::SdJwt => {
+            if ssh_agent_sock_opt.is_some() {
+                bail!("ssh-agent signing is not supported for SD-JWT issuance");
+            }
+            let mut value = serde_json::to_value(&credential)?;
+            let disclosures = match value.get_mut("credentialSubject").and_then(Value::as_object_mut) {
+                Some(subject) => sd_jwt::redact_claims(subject, &args.sd_claims),
+                None => Vec::new(),
+            };
+            let redacted: VerifiableCredential = serde_json::from_value(value)?;
+            let jwt = redacted
+                .generate_jwt(jwk_opt.as_ref(), &options, &resolver)
+                .await?;
+            let mut parts: Vec<String> = std::iter::once(jwt)
+                .chain(disclosures.into_iter().map(|d| d.encoded))
+                .collect();
+            parts.push(String::new());
+            print!("{}", parts.join("~"));
         }
         _ => {
-            panic!("Unknown proof format: {:?}", proof_format);
+            bail!("unsupported proof format: {:?}", proof_format);
         }
     }
     Ok(())
@@ -121,8 +212,8 @@ pub async fn get_nquad_positions(args: CredentialQueryArgs) -> Result<()> {
 
     let credential_reader = BufReader::new(stdin());
     let mut context_loader = ContextLoader::default();
-    let credential: VerifiableCredential =
-        serde_json::from_reader(credential_reader).unwrap();
+    let credential: VerifiableCredential = serde_json::from_reader(credential_reader)
+        .context("unable to parse credential JSON from stdin")?;
 
     let positions = credential.get_nquad_positions(&args.selectors, &mut context_loader).await?;
     let strings: Vec<String> = positions.into_iter().map(|position| position.to_string()).collect();
@@ -131,21 +222,38 @@ pub async fn get_nquad_positions(args: CredentialQueryArgs) -> Result<()> {
 }
 
 pub async fn derive(args: CredentialDeriveArgs) -> Result<()> {
-    let credential_reader = BufReader::new(stdin());
-    let mut credential: VerifiableCredential =
-        serde_json::from_reader(credential_reader).unwrap();
+    let mut input = String::new();
+    stdin().read_to_string(&mut input)?;
+    let input = input.trim();
 
+    // A credential is a JSON object; a combined SD-JWT presentation never parses as one
+    // (it's a JWT followed by `~`-separated disclosures), so use that to dispatch rather
+    // than a `~` substring check, which a credential's own string values could contain.
+    let credential_value: Option<Value> = serde_json::from_str(input).ok();
+    let Some(credential_value) = credential_value else {
+        let derived = sd_jwt::select_disclosures(input, &args.selectors)?;
+        print!("{derived}");
+        return Ok(());
+    };
+
+    let mut credential: VerifiableCredential = serde_json::from_value(credential_value)
+        .context("unable to parse credential JSON from stdin")?;
+
+    let proof_nonce = args
+        .proof_nonce
+        .as_deref()
+        .ok_or_else(|| anyhow!("--proof-nonce is required to derive a BBS+ proof"))?;
     let did_resolver = didkit::DID_METHODS.to_resolver();
 
     let derived_credential = ssi::vc::derive_credential(
         &credential,
-        &args.proof_nonce,
+        proof_nonce,
         &args.selectors.as_slice(),
         did_resolver
-    ).await.unwrap();
+    ).await?;
 
     let stdout_writer = BufWriter::new(stdout());
-    serde_json::to_writer(stdout_writer, &derived_credential).unwrap();
+    serde_json::to_writer(stdout_writer, &derived_credential)?;
     Ok(())
 }
 
@@ -155,10 +263,47 @@ pub async fn verify(args: CredentialVerifyArgs) -> Result<()> {
     let mut credential_reader = BufReader::new(stdin());
     let proof_format = args.proof_options.proof_format.clone();
     let options = LinkedDataProofOptions::from(args.proof_options);
+
+    // Handled separately from the other formats: on success, the reconstructed
+    // `credentialSubject` the disclosures revealed is merged into the printed result, which
+    // the other formats' `VerificationResult` has no field for.
+    if let ProofFormat::SdJwt = proof_format {
+        let mut sd_jwt = String::new();
+        credential_reader.read_to_string(&mut sd_jwt)?;
+        let (jwt, disclosures, _key_binding_jwt) = sd_jwt::split(sd_jwt.trim());
+
+        let mut result =
+            VerifiableCredential::verify_jwt(jwt, Some(options), &resolver, &mut context_loader)
+                .await;
+        let mut revealed_subject = None;
+        if result.errors.is_empty() {
+            match sd_jwt::verify_disclosures(jwt, &disclosures) {
+                Ok(payload) => {
+                    revealed_subject = payload
+                        .get("vc")
+                        .and_then(|vc| vc.get("credentialSubject"))
+                        .cloned()
+                }
+                Err(e) => result.errors.push(e.to_string()),
+            }
+        }
+
+        let mut output = serde_json::to_value(&result)?;
+        if let Some(output) = output.as_object_mut() {
+            output.insert("credentialSubject".to_string(), json!(revealed_subject));
+        }
+        let stdout_writer = BufWriter::new(stdout());
+        serde_json::to_writer(stdout_writer, &output)?;
+        if !result.errors.is_empty() {
+            std::process::exit(error::ERROR_EXIT_CODE);
+        }
+        return Ok(());
+    }
+
     let result = match proof_format {
         ProofFormat::JWT => {
             let mut jwt = String::new();
-            credential_reader.read_to_string(&mut jwt).unwrap();
+            credential_reader.read_to_string(&mut jwt)?;
             let trimmed_jwt = jwt.trim();
             if jwt != trimmed_jwt {
                 warn!("JWT was trimmed for extraneous whitespaces and new lines.");
@@ -172,23 +317,35 @@ pub async fn verify(args: CredentialVerifyArgs) -> Result<()> {
             .await
         }
         ProofFormat::LDP => {
-            let credential: VerifiableCredential =
-                serde_json::from_reader(credential_reader).unwrap();
-            credential.validate_unsigned().unwrap();
-            // todo this needs to be updated with disclosed messages from command line
-            credential
-                .verify(Some(options), &resolver, &mut context_loader)
+            let credential: VerifiableCredential = serde_json::from_reader(credential_reader)
+                .context("unable to parse credential JSON from stdin")?;
+            credential.validate_unsigned()?;
+            if args.disclosed.is_empty() {
+                credential
+                    .verify(Some(options), &resolver, &mut context_loader)
+                    .await
+            } else {
+                // Verifying a BBS+ derived proof requires the exact n-quad statements a
+                // prior `derive` revealed; pair with `query`'s `get_nquad_positions`.
+                ssi::vc::verify_credential_with_disclosed(
+                    &credential,
+                    Some(options),
+                    &resolver,
+                    &mut context_loader,
+                    &args.disclosed,
+                )
                 .await
+            }
         }
         _ => {
-            panic!("Unknown proof format: {:?}", proof_format);
+            bail!("unsupported proof format: {:?}", proof_format);
         }
     };
 
     let stdout_writer = BufWriter::new(stdout());
-    serde_json::to_writer(stdout_writer, &result).unwrap();
+    serde_json::to_writer(stdout_writer, &result)?;
     if !result.errors.is_empty() {
-        std::process::exit(2);
+        std::process::exit(error::ERROR_EXIT_CODE);
     }
     Ok(())
 }
@@ -0,0 +1,30 @@
+//! Shared error reporting for the credential/presentation subcommands: malformed
+//! input, an unsupported proof format, and similar failures are reported as
+//! `{"errors": [...]}` (or a plain line in `--format text`) on a stable nonzero
+//! exit code, rather than an `unwrap`/`panic!` backtrace.
+
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+/// The exit code used for both parse/format errors and verification failures.
+pub const ERROR_EXIT_CODE: i32 = 2;
+
+/// Render `error` per `format`, then exit with [`ERROR_EXIT_CODE`].
+pub fn report_and_exit(format: OutputFormat, error: anyhow::Error) -> ! {
+    match format {
+        OutputFormat::Json => {
+            let body = serde_json::json!({ "errors": [error.to_string()] });
+            println!("{}", serde_json::to_string(&body).expect("JSON object always serializes"));
+        }
+        OutputFormat::Text => {
+            eprintln!("Error: {error}");
+        }
+    }
+    std::process::exit(ERROR_EXIT_CODE);
+}
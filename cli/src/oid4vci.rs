@@ -0,0 +1,264 @@
+//! Minimal support for OpenID for Verifiable Credential Issuance (OID4VCI):
+//! turning an already-issued credential into a credential offer a wallet can
+//! redeem, plus the request/response handling for an issuer's `/credential`
+//! endpoint. Binding `handle_credential_request` to an actual HTTP listener
+//! is left to the project's server front end; the `Request` subcommand
+//! exercises it directly so it's reachable without one.
+
+use std::io::{stdin, BufReader};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use didkit::{
+    generate_proof, ssi, ContextLoader, LinkedDataProofOptions, VerifiableCredential, JWK,
+};
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+use crate::{error::OutputFormat, get_ssh_agent_sock, opts::ResolverOptions, KeyArg, ProofOptions};
+
+#[derive(Args)]
+pub struct CredentialOfferArgs {
+    /// Base URL this issuer is reachable at, used in the offer and metadata document
+    #[clap(long)]
+    credential_issuer: String,
+    /// Credential type to advertise in the offer and metadata document, overriding the
+    /// type inferred from the credential's own `type` property
+    #[clap(long)]
+    credential_type: Option<String>,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct CredentialRequestArgs {
+    #[clap(flatten)]
+    key: KeyArg,
+    #[clap(flatten)]
+    proof_options: ProofOptions,
+    #[clap(flatten)]
+    resolver_options: ResolverOptions,
+    /// Base URL this issuer is reachable at, checked against the proof-of-possession JWT's
+    /// `aud` claim
+    #[clap(long)]
+    credential_issuer: String,
+    /// Nonce this issuer previously handed the wallet (e.g. in a token response's
+    /// `c_nonce`), checked against the proof-of-possession JWT's `nonce` claim
+    #[clap(long)]
+    expected_nonce: String,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize)]
+pub struct CredentialOffer {
+    pub credential_issuer: String,
+    pub credentials: Vec<String>,
+    pub grants: CredentialOfferGrants,
+}
+
+#[derive(Serialize)]
+pub struct CredentialOfferGrants {
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:pre-authorized_code")]
+    pub pre_authorized_code: PreAuthorizedCodeGrant,
+}
+
+#[derive(Serialize)]
+pub struct PreAuthorizedCodeGrant {
+    #[serde(rename = "pre-authorized_code")]
+    pub pre_authorized_code: String,
+}
+
+fn generate_pre_authorized_code() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Metadata document to be served at `/.well-known/openid-credential-issuer`.
+pub fn issuer_metadata(credential_issuer: &str, credential_type: &str) -> Value {
+    json!({
+        "credential_issuer": credential_issuer,
+        "credential_endpoint": format!("{credential_issuer}/credential"),
+        "credentials_supported": [
+            { "id": credential_type, "format": "jwt_vc_json", "types": [credential_type] },
+            { "id": format!("{credential_type}-ldp"), "format": "ldp_vc", "types": [credential_type] },
+        ],
+    })
+}
+
+/// Read an already-issued credential from stdin and print the `credential_offer` object a
+/// wallet would scan or follow a deep link to, alongside the issuer metadata document a
+/// wallet fetches to learn how to redeem it, wrapping a fresh pre-authorized code.
+pub async fn offer(args: CredentialOfferArgs) -> Result<()> {
+    let credential_reader = BufReader::new(stdin());
+    let credential: VerifiableCredential = serde_json::from_reader(credential_reader)
+        .context("unable to parse credential JSON from stdin")?;
+    // Most credentials carry `["VerifiableCredential", "SomeSpecificCredential", ...]`, so
+    // `to_single()` (which only succeeds for exactly one entry) would fall through to the
+    // base type for virtually every real credential; take the most specific (last non-base)
+    // entry instead, unless the caller named one explicitly.
+    let credential_type = args.credential_type.clone().unwrap_or_else(|| {
+        credential
+            .type_
+            .clone()
+            .into_iter()
+            .filter(|t| t != "VerifiableCredential")
+            .last()
+            .unwrap_or_else(|| "VerifiableCredential".to_string())
+    });
+
+    let credential_offer = CredentialOffer {
+        credential_issuer: args.credential_issuer.clone(),
+        credentials: vec![credential_type.clone()],
+        grants: CredentialOfferGrants {
+            pre_authorized_code: PreAuthorizedCodeGrant {
+                pre_authorized_code: generate_pre_authorized_code(),
+            },
+        },
+    };
+    let output = json!({
+        "credential_offer": credential_offer,
+        "issuer_metadata": issuer_metadata(&args.credential_issuer, &credential_type),
+    });
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
+
+/// The JSON object of a compact JWS's header, decoded without verifying the signature.
+fn decode_header(jwt: &str) -> Result<Value> {
+    let header = jwt
+        .split('.')
+        .next()
+        .ok_or_else(|| anyhow!("malformed proof-of-possession JWT"))?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(header)
+        .context("invalid proof-of-possession JWT header encoding")?;
+    serde_json::from_slice(&bytes).context("invalid proof-of-possession JWT header")
+}
+
+/// Resolve the holder's key from the proof JWT's `kid`, verify the JWS against it, and
+/// check that it was minted for this issuer (`aud`) and this request (`nonce`).
+async fn verify_proof_of_possession(
+    proof_jwt: &str,
+    credential_issuer: &str,
+    expected_nonce: &str,
+    resolver: &dyn ssi::did_resolve::DIDResolver,
+) -> Result<()> {
+    let kid = decode_header(proof_jwt)?
+        .get("kid")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("proof-of-possession JWT header is missing `kid`"))?
+        .to_string();
+    let jwk = ssi::did_resolve::resolve_key(&kid, resolver)
+        .await
+        .context("unable to resolve holder key named by proof-of-possession JWT `kid`")?;
+    let payload_bytes = ssi::jws::decode_verify(proof_jwt, &jwk)
+        .context("proof-of-possession JWT signature verification failed")?;
+    let payload: Value = serde_json::from_slice(&payload_bytes)
+        .context("invalid proof-of-possession JWT payload")?;
+    if payload.get("aud").and_then(Value::as_str) != Some(credential_issuer) {
+        bail!("proof-of-possession JWT `aud` does not match this issuer");
+    }
+    if payload.get("nonce").and_then(Value::as_str) != Some(expected_nonce) {
+        bail!("proof-of-possession JWT `nonce` does not match the nonce issued to this wallet");
+    }
+    Ok(())
+}
+
+/// Handle one `/credential` request against a fixed, already-issued credential: verify
+/// the wallet's proof of possession, then sign and return the credential in whichever
+/// format (`jwt_vc_json` or `ldp_vc`) the wallet asked for.
+pub async fn handle_credential_request(
+    credential: &VerifiableCredential,
+    jwk: Option<&JWK>,
+    options: &LinkedDataProofOptions,
+    resolver: &dyn ssi::did_resolve::DIDResolver,
+    context_loader: &mut ContextLoader,
+    ssh_agent_sock: Option<&str>,
+    request: &Value,
+    credential_issuer: &str,
+    expected_nonce: &str,
+) -> Result<Value> {
+    let proof_jwt = request
+        .get("proof")
+        .and_then(|proof| proof.get("jwt"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing proof-of-possession JWT"))?;
+    verify_proof_of_possession(proof_jwt, credential_issuer, expected_nonce, resolver).await?;
+
+    let format = request
+        .get("format")
+        .and_then(Value::as_str)
+        .unwrap_or("jwt_vc_json");
+    let body = match format {
+        "ldp_vc" => {
+            let mut credential = credential.clone();
+            let proof = generate_proof(
+                &credential,
+                jwk,
+                options.clone(),
+                resolver,
+                context_loader,
+                ssh_agent_sock,
+            )
+            .await?;
+            credential.add_proof(proof);
+            json!({ "format": "ldp_vc", "credential": credential })
+        }
+        _ => {
+            let jwt = credential.generate_jwt(jwk, options, resolver).await?;
+            json!({ "format": "jwt_vc_json", "credential": jwt })
+        }
+    };
+    Ok(body)
+}
+
+/// Read a `{"credential": ..., "request": ...}` object from stdin — the already-issued
+/// credential an earlier `offer` was generated from, and the wallet's `/credential` request
+/// body — and print the response `handle_credential_request` would hand back over HTTP.
+pub async fn request(args: CredentialRequestArgs) -> Result<()> {
+    let resolver = args.resolver_options.to_resolver();
+    let mut context_loader = ContextLoader::default();
+    let input: Value = serde_json::from_reader(BufReader::new(stdin()))
+        .context("unable to parse credential request JSON from stdin")?;
+    let credential: VerifiableCredential = input
+        .get("credential")
+        .cloned()
+        .ok_or_else(|| anyhow!("missing `credential` to issue"))
+        .and_then(|value| {
+            serde_json::from_value(value).context("unable to parse `credential`")
+        })?;
+    let request = input
+        .get("request")
+        .cloned()
+        .ok_or_else(|| anyhow!("missing `request` (the wallet's /credential request body)"))?;
+
+    let jwk_opt: Option<JWK> = args.key.get_jwk_opt();
+    let ssh_agent_sock_opt = if args.key.ssh_agent {
+        Some(get_ssh_agent_sock())
+    } else {
+        None
+    };
+    let options = LinkedDataProofOptions::from(args.proof_options);
+
+    let response = handle_credential_request(
+        &credential,
+        jwk_opt.as_ref(),
+        &options,
+        &resolver,
+        &mut context_loader,
+        ssh_agent_sock_opt.as_deref(),
+        &request,
+        &args.credential_issuer,
+        &args.expected_nonce,
+    )
+    .await?;
+    println!("{}", serde_json::to_string(&response)?);
+    Ok(())
+}
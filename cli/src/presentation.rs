@@ -0,0 +1,267 @@
+use std::io::{stdin, stdout, BufReader, BufWriter, Read};
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Subcommand};
+use didkit::{
+    generate_proof, ssi, ContextLoader, LinkedDataProofOptions, ProofFormat, VerifiableCredential,
+    VerifiablePresentation, JWK,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    error::{self, report_and_exit, OutputFormat},
+    get_ssh_agent_sock,
+    opts::ResolverOptions,
+    sd_jwt, KeyArg, ProofOptions,
+};
+
+/// `options` without `challenge`/`domain`: those bind the holder's presentation proof to
+/// a verifier-issued nonce and normally aren't present on the embedded credentials'
+/// issuer-generated proofs, so reusing them verbatim would fail every embedded
+/// credential whenever the caller passed `--challenge`/`--domain` for replay protection.
+fn credential_proof_options(options: &LinkedDataProofOptions) -> LinkedDataProofOptions {
+    let mut credential_options = options.clone();
+    credential_options.challenge = None;
+    credential_options.domain = None;
+    credential_options
+}
+
+/// The `verifiableCredential` entries embedded in a JWT-encoded presentation's `vp` claim.
+fn embedded_credentials_from_jwt(jwt: &str) -> Result<Vec<Value>> {
+    let payload = sd_jwt::decode_payload(jwt)?;
+    let credentials = payload
+        .get("vp")
+        .and_then(|vp| vp.get("verifiableCredential"))
+        .cloned()
+        .unwrap_or(Value::Null);
+    Ok(match credentials {
+        Value::Array(items) => items,
+        Value::Null => Vec::new(),
+        other => vec![other],
+    })
+}
+
+/// Verify one embedded credential, which may be a bare JWT string or an embedded LDP
+/// credential object, returning its verification errors (empty on success).
+async fn verify_embedded_credential(
+    credential: Value,
+    options: &LinkedDataProofOptions,
+    resolver: &dyn ssi::did_resolve::DIDResolver,
+    context_loader: &mut ContextLoader,
+) -> Vec<String> {
+    match credential {
+        Value::String(jwt) => {
+            VerifiableCredential::verify_jwt(&jwt, Some(options.clone()), resolver, context_loader)
+                .await
+                .errors
+        }
+        other => match serde_json::from_value::<VerifiableCredential>(other) {
+            Ok(credential) => {
+                credential
+                    .verify(Some(options.clone()), resolver, context_loader)
+                    .await
+                    .errors
+            }
+            Err(e) => vec![e.to_string()],
+        },
+    }
+}
+
+#[derive(Subcommand)]
+pub enum PresentationCmd {
+    /// Issue Presentation
+    Issue(Box<PresentationIssueArgs>),
+    /// Verify Presentation
+    Verify(PresentationVerifyArgs),
+}
+
+#[derive(Args)]
+pub struct PresentationIssueArgs {
+    #[clap(flatten)]
+    key: KeyArg,
+    #[clap(flatten)]
+    proof_options: ProofOptions,
+    #[clap(flatten)]
+    resolver_options: ResolverOptions,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct PresentationVerifyArgs {
+    #[clap(flatten)]
+    proof_options: ProofOptions,
+    #[clap(flatten)]
+    resolver_options: ResolverOptions,
+    /// Format for reporting errors
+    #[clap(long, value_enum, default_value_t)]
+    format: OutputFormat,
+}
+
+pub async fn cli(cmd: PresentationCmd) -> Result<()> {
+    match cmd {
+        PresentationCmd::Issue(cmd_issue) => {
+            let format = cmd_issue.format;
+            issue(*cmd_issue)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+        PresentationCmd::Verify(cmd_verify) => {
+            let format = cmd_verify.format;
+            verify(cmd_verify)
+                .await
+                .unwrap_or_else(|e| report_and_exit(format, e));
+        }
+    };
+    Ok(())
+}
+
+/// Read a presentation from stdin, accepting either a full `VerifiablePresentation` or a
+/// bare list of credentials to wrap into one.
+fn read_presentation() -> Result<VerifiablePresentation> {
+    let input: Value = serde_json::from_reader(BufReader::new(stdin()))
+        .context("unable to parse presentation JSON from stdin")?;
+    let presentation = match input {
+        Value::Array(credentials) => serde_json::from_value(json!({
+            "@context": ["https://www.w3.org/2018/credentials/v1"],
+            "type": "VerifiablePresentation",
+            "verifiableCredential": credentials,
+        }))?,
+        other => serde_json::from_value(other)?,
+    };
+    Ok(presentation)
+}
+
+pub async fn issue(args: PresentationIssueArgs) -> Result<()> {
+    let resolver = args.resolver_options.to_resolver();
+    let mut context_loader = ContextLoader::default();
+    let mut presentation = read_presentation()?;
+    let proof_format = args.proof_options.proof_format.clone();
+    let jwk_opt: Option<JWK> = args.key.get_jwk_opt();
+    let ssh_agent_sock_opt = if args.key.ssh_agent {
+        Some(get_ssh_agent_sock())
+    } else {
+        None
+    };
+    let options = LinkedDataProofOptions::from(args.proof_options);
+    match proof_format {
+        ProofFormat::JWT => {
+            let jwt = match ssh_agent_sock_opt.as_deref() {
+                Some(ssh_agent_sock) => {
+                    presentation
+                        .generate_jwt_with_ssh_agent(
+                            jwk_opt.as_ref(),
+                            &options,
+                            &resolver,
+                            ssh_agent_sock,
+                        )
+                        .await?
+                }
+                None => {
+                    presentation
+                        .generate_jwt(jwk_opt.as_ref(), &options, &resolver)
+                        .await?
+                }
+            };
+            print!("{jwt}");
+        }
+        ProofFormat::LDP => {
+            let proof = generate_proof(
+                &presentation,
+                jwk_opt.as_ref(),
+                options,
+                &resolver,
+                &mut context_loader,
+                ssh_agent_sock_opt.as_deref(),
+            )
+            .await?;
+            presentation.add_proof(proof);
+            let stdout_writer = BufWriter::new(stdout());
+            serde_json::to_writer(stdout_writer, &presentation)?;
+        }
+        _ => {
+            bail!("unsupported proof format: {:?}", proof_format);
+        }
+    }
+    Ok(())
+}
+
+pub async fn verify(args: PresentationVerifyArgs) -> Result<()> {
+    let resolver = args.resolver_options.to_resolver();
+    let mut context_loader = ContextLoader::default();
+    let mut presentation_reader = BufReader::new(stdin());
+    let proof_format = args.proof_options.proof_format.clone();
+    let options = LinkedDataProofOptions::from(args.proof_options);
+    let result = match proof_format {
+        ProofFormat::JWT => {
+            let mut jwt = String::new();
+            presentation_reader.read_to_string(&mut jwt)?;
+            let trimmed_jwt = jwt.trim();
+            let mut result = VerifiablePresentation::verify_jwt(
+                trimmed_jwt,
+                Some(options.clone()),
+                &resolver,
+                &mut context_loader,
+            )
+            .await;
+            let credential_options = credential_proof_options(&options);
+            match embedded_credentials_from_jwt(trimmed_jwt) {
+                Ok(credentials) => {
+                    for credential in credentials {
+                        result.errors.extend(
+                            verify_embedded_credential(
+                                credential,
+                                &credential_options,
+                                &resolver,
+                                &mut context_loader,
+                            )
+                            .await,
+                        );
+                    }
+                }
+                Err(e) => result.errors.push(e.to_string()),
+            }
+            result
+        }
+        ProofFormat::LDP => {
+            let presentation: VerifiablePresentation = serde_json::from_reader(presentation_reader)
+                .context("unable to parse presentation JSON from stdin")?;
+            presentation.validate_unsigned()?;
+            let mut result = presentation
+                .verify(Some(options.clone()), &resolver, &mut context_loader)
+                .await;
+            let credential_options = credential_proof_options(&options);
+            for credential in presentation.verifiable_credential.clone().into_iter().flatten() {
+                let credential_result = match credential {
+                    ssi::vc::CredentialOrJWT::Credential(credential) => {
+                        credential
+                            .verify(Some(credential_options.clone()), &resolver, &mut context_loader)
+                            .await
+                    }
+                    ssi::vc::CredentialOrJWT::JWT(jwt) => {
+                        VerifiableCredential::verify_jwt(
+                            &jwt,
+                            Some(credential_options.clone()),
+                            &resolver,
+                            &mut context_loader,
+                        )
+                        .await
+                    }
+                };
+                result.errors.extend(credential_result.errors);
+            }
+            result
+        }
+        _ => {
+            bail!("unsupported proof format: {:?}", proof_format);
+        }
+    };
+
+    let stdout_writer = BufWriter::new(stdout());
+    serde_json::to_writer(stdout_writer, &result)?;
+    if !result.errors.is_empty() {
+        std::process::exit(error::ERROR_EXIT_CODE);
+    }
+    Ok(())
+}
@@ -0,0 +1,415 @@
+//! Helpers implementing the disclosure/digest mechanics of SD-JWT
+//! (Selective Disclosure for JWTs), used by the `SdJwt` proof format in
+//! [`crate::credential`].
+//!
+//! A claim disclosure is `base64url(JSON [salt, claim_name, claim_value])`;
+//! its digest replaces the claim in the JWT payload inside an `_sd` array,
+//! alongside an `_sd_alg` claim naming the hash used. An array-element
+//! disclosure is `base64url(JSON [salt, value])`; its digest replaces the
+//! element in place as `{"...": <digest>}`. In both cases the digest is
+//! `base64url(SHA-256(ascii(disclosure)))`.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use rand::RngCore;
+use serde_json::{json, Map, Value};
+use sha2::{Digest as _, Sha256};
+
+/// A single selectively-disclosable claim or array element, carved out of a JSON value.
+pub struct Disclosure {
+    /// `Some("name")` for a claim disclosure, `Some("name[index]")` for an array-element
+    /// disclosure (for diagnostics only — the wire format carries no name for the latter).
+    pub name: Option<String>,
+    pub encoded: String,
+}
+
+/// A selector identifying either a top-level claim (`"name"`) or a single element of a
+/// top-level array-valued claim (`"name[index]"`).
+enum Selector {
+    Claim(String),
+    ArrayElement(String, usize),
+}
+
+fn parse_selector(selector: &str) -> Selector {
+    if let Some(body) = selector.strip_suffix(']') {
+        if let Some(bracket) = body.find('[') {
+            if let Ok(index) = body[bracket + 1..].parse::<usize>() {
+                return Selector::ArrayElement(body[..bracket].to_string(), index);
+            }
+        }
+    }
+    Selector::Claim(selector.to_string())
+}
+
+fn random_salt() -> String {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    URL_SAFE_NO_PAD.encode(salt)
+}
+
+fn encode_array(values: &[Value]) -> String {
+    URL_SAFE_NO_PAD.encode(
+        serde_json::to_vec(&Value::Array(values.to_vec())).expect("JSON array always serializes"),
+    )
+}
+
+/// SHA-256 digest of a disclosure's base64url encoding, itself base64url-encoded.
+pub fn digest(disclosure: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(disclosure.as_bytes());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+/// Remove the claims and array elements named by `selectors` from `object`, replacing
+/// top-level claims with digests in an `_sd` array and array elements with `{"...":
+/// <digest>}` in place, and return the disclosures that were carved out.
+pub fn redact_claims(object: &mut Map<String, Value>, selectors: &[String]) -> Vec<Disclosure> {
+    let mut disclosures = Vec::new();
+    let mut sd_digests = Vec::new();
+    for selector in selectors {
+        match parse_selector(selector) {
+            Selector::Claim(name) => {
+                let Some(value) = object.remove(&name) else {
+                    continue;
+                };
+                let encoded = encode_array(&[
+                    Value::String(random_salt()),
+                    Value::String(name.clone()),
+                    value,
+                ]);
+                sd_digests.push(Value::String(digest(&encoded)));
+                disclosures.push(Disclosure {
+                    name: Some(name),
+                    encoded,
+                });
+            }
+            Selector::ArrayElement(name, index) => {
+                let Some(Value::Array(items)) = object.get_mut(&name) else {
+                    continue;
+                };
+                let Some(slot) = items.get_mut(index) else {
+                    continue;
+                };
+                let encoded = encode_array(&[Value::String(random_salt()), slot.clone()]);
+                let element_digest = digest(&encoded);
+                *slot = json!({ "...": element_digest });
+                disclosures.push(Disclosure {
+                    name: Some(format!("{name}[{index}]")),
+                    encoded,
+                });
+            }
+        }
+    }
+    if !sd_digests.is_empty() {
+        object.insert("_sd".into(), Value::Array(sd_digests));
+    }
+    if !disclosures.is_empty() {
+        object.insert("_sd_alg".into(), Value::String("sha-256".into()));
+    }
+    disclosures
+}
+
+/// Split a combined SD-JWT presentation (`<jwt>~<disclosure>~...~[<kb-jwt>]`)
+/// into its issuer JWT, its disclosures (in order), and an optional
+/// trailing key-binding JWT.
+pub fn split(sd_jwt: &str) -> (&str, Vec<&str>, Option<&str>) {
+    let mut segments: Vec<&str> = sd_jwt.split('~').collect();
+    let jwt = segments.remove(0);
+    // A well-formed combined serialization ends in a bare "~"; a key-binding
+    // JWT, if present, follows that final separator instead.
+    let key_binding_jwt = match segments.last() {
+        Some(last) if !last.is_empty() => Some(*last),
+        _ => None,
+    };
+    if key_binding_jwt.is_some() {
+        segments.pop();
+    }
+    segments.retain(|segment| !segment.is_empty());
+    (jwt, segments, key_binding_jwt)
+}
+
+/// Recursively collect every digest named in an `_sd` array, or in an array element's
+/// `{"...": digest}` marker, anywhere in `value`.
+fn collect_sd_digests(value: &Value, digests: &mut HashSet<String>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(sd)) = object.get("_sd") {
+                digests.extend(sd.iter().filter_map(Value::as_str).map(str::to_string));
+            }
+            if let Some(Value::String(d)) = object.get("...") {
+                digests.insert(d.clone());
+            }
+            for (key, nested) in object {
+                if key != "_sd" && key != "..." {
+                    collect_sd_digests(nested, digests);
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_sd_digests(item, digests)),
+        _ => {}
+    }
+}
+
+/// Decode the (unverified) JSON payload of a compact JWT.
+pub(crate) fn decode_payload(jwt: &str) -> Result<Value> {
+    let payload = jwt
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| anyhow!("malformed JWT: missing payload segment"))?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// The claim name of a claim disclosure (`[salt, name, value]`); `None` for an
+/// array-element disclosure (`[salt, value]`), which carries no name on the wire.
+fn disclosure_claim_name(disclosure: &str) -> Result<Option<String>> {
+    let bytes = URL_SAFE_NO_PAD.decode(disclosure)?;
+    let array: Value = serde_json::from_slice(&bytes)?;
+    match array.as_array().map(Vec::len) {
+        Some(3) => Ok(Some(
+            array
+                .get(1)
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("malformed SD-JWT disclosure"))?
+                .to_string(),
+        )),
+        Some(2) => Ok(None),
+        _ => bail!("malformed SD-JWT disclosure"),
+    }
+}
+
+/// Decode a disclosure into its digest and the claim/element it reveals: `name` is `Some`
+/// for a claim disclosure (`[salt, name, value]`), `None` for an array-element disclosure
+/// (`[salt, value]`), which carries no name on the wire.
+fn decode_disclosure(disclosure: &str) -> Result<(String, Option<String>, Value)> {
+    let bytes = URL_SAFE_NO_PAD.decode(disclosure)?;
+    let array: Value = serde_json::from_slice(&bytes)?;
+    let items = array.as_array().ok_or_else(|| anyhow!("malformed SD-JWT disclosure"))?;
+    let d = digest(disclosure);
+    match items.as_slice() {
+        [_, name, value] => {
+            let name = name.as_str().ok_or_else(|| anyhow!("malformed SD-JWT disclosure"))?;
+            Ok((d, Some(name.to_string()), value.clone()))
+        }
+        [_, value] => Ok((d, None, value.clone())),
+        _ => bail!("malformed SD-JWT disclosure"),
+    }
+}
+
+/// Replace every `_sd` digest and `{"...": digest}` marker in `value` whose digest is in
+/// `revealed` with the claim/element it reveals, leaving digests not in `revealed` (i.e.
+/// not disclosed) untouched.
+fn reveal_disclosures(value: &mut Value, revealed: &HashMap<String, (Option<String>, Value)>) {
+    if let Value::Object(object) = &*value {
+        if let Some(d) = object.get("...").and_then(Value::as_str) {
+            if let Some((_, element_value)) = revealed.get(d) {
+                let element_value = element_value.clone();
+                *value = element_value;
+                return;
+            }
+        }
+    }
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(sd)) = object.get("_sd").cloned() {
+                let mut remaining = Vec::new();
+                for entry in sd {
+                    match entry.as_str().and_then(|d| revealed.get(d)) {
+                        Some((Some(name), claim_value)) => {
+                            object.insert(name.clone(), claim_value.clone());
+                        }
+                        _ => remaining.push(entry),
+                    }
+                }
+                if remaining.is_empty() {
+                    object.remove("_sd");
+                } else {
+                    object.insert("_sd".into(), Value::Array(remaining));
+                }
+            }
+            for nested in object.values_mut() {
+                reveal_disclosures(nested, revealed);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| reveal_disclosures(item, revealed)),
+        _ => {}
+    }
+}
+
+/// Check that every disclosure's digest actually appears in an `_sd` claim or array
+/// element of the issuer JWT's payload, then reconstruct the revealed claims/elements in
+/// place, returning the resulting payload (undisclosed claims remain opaque `_sd` digests).
+pub fn verify_disclosures(jwt: &str, disclosures: &[&str]) -> Result<Value> {
+    let mut payload = decode_payload(jwt)?;
+    let mut known = HashSet::new();
+    collect_sd_digests(&payload, &mut known);
+
+    let mut revealed = HashMap::new();
+    for disclosure in disclosures {
+        let (d, name, value) = decode_disclosure(disclosure)?;
+        if !known.contains(&d) {
+            bail!("disclosure digest {d} is not present in any _sd claim or array element");
+        }
+        revealed.insert(d, (name, value));
+    }
+    reveal_disclosures(&mut payload, &revealed);
+    Ok(payload)
+}
+
+/// Drop every claim disclosure whose claim name is not in `selectors`, returning the
+/// trimmed combined serialization. Array-element disclosures carry no claim name on the
+/// wire (per the SD-JWT spec, `[salt, value]`), so they can't be matched against
+/// `selectors` here and are always kept; callers that need to withhold them must do so
+/// at issuance time via `redact_claims` instead.
+pub fn select_disclosures(sd_jwt: &str, selectors: &[String]) -> Result<String> {
+    let (jwt, disclosures, key_binding_jwt) = split(sd_jwt);
+    let mut kept = vec![jwt.to_string()];
+    for disclosure in disclosures {
+        let keep = match disclosure_claim_name(disclosure)? {
+            Some(name) => selectors.iter().any(|s| s == &name),
+            None => true,
+        };
+        if keep {
+            kept.push(disclosure.to_string());
+        }
+    }
+    let mut combined = format!("{}~", kept.join("~"));
+    if let Some(kb_jwt) = key_binding_jwt {
+        combined.push_str(kb_jwt);
+    }
+    Ok(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_claims_moves_claim_into_sd_array() {
+        let mut subject = Map::new();
+        subject.insert("name".into(), json!("Alice"));
+        subject.insert("age".into(), json!(30));
+
+        let disclosures = redact_claims(&mut subject, &["name".to_string()]);
+
+        assert_eq!(disclosures.len(), 1);
+        assert_eq!(disclosures[0].name.as_deref(), Some("name"));
+        assert!(!subject.contains_key("name"));
+        assert_eq!(subject["age"], json!(30));
+        assert_eq!(subject["_sd_alg"], json!("sha-256"));
+        let sd = subject["_sd"].as_array().unwrap();
+        assert_eq!(sd.len(), 1);
+        assert_eq!(sd[0].as_str().unwrap(), digest(&disclosures[0].encoded));
+    }
+
+    #[test]
+    fn redact_claims_hides_array_element_in_place() {
+        let mut subject = Map::new();
+        subject.insert("achievements".into(), json!(["scuba", "skydiving"]));
+
+        let disclosures = redact_claims(&mut subject, &["achievements[1]".to_string()]);
+
+        assert_eq!(disclosures.len(), 1);
+        assert_eq!(disclosures[0].name.as_deref(), Some("achievements[1]"));
+        let achievements = subject["achievements"].as_array().unwrap();
+        assert_eq!(achievements[0], json!("scuba"));
+        assert_eq!(
+            achievements[1]["..."].as_str().unwrap(),
+            digest(&disclosures[0].encoded)
+        );
+        // Array-element disclosures aren't named in `_sd`.
+        assert!(!subject.contains_key("_sd"));
+    }
+
+    #[test]
+    fn split_parses_disclosures_and_key_binding_jwt() {
+        let combined = "header.payload.sig~disclosure1~disclosure2~kb.jwt.sig";
+        let (jwt, disclosures, kb_jwt) = split(combined);
+        assert_eq!(jwt, "header.payload.sig");
+        assert_eq!(disclosures, vec!["disclosure1", "disclosure2"]);
+        assert_eq!(kb_jwt, Some("kb.jwt.sig"));
+    }
+
+    #[test]
+    fn split_without_key_binding_jwt_has_trailing_tilde() {
+        let combined = "header.payload.sig~disclosure1~";
+        let (jwt, disclosures, kb_jwt) = split(combined);
+        assert_eq!(jwt, "header.payload.sig");
+        assert_eq!(disclosures, vec!["disclosure1"]);
+        assert_eq!(kb_jwt, None);
+    }
+
+    fn jwt_with_sd(sd_digests: &[String]) -> String {
+        let payload = json!({ "_sd": sd_digests, "_sd_alg": "sha-256" });
+        let header = URL_SAFE_NO_PAD.encode(b"{}");
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload).unwrap());
+        format!("{header}.{payload}.sig")
+    }
+
+    #[test]
+    fn verify_disclosures_accepts_known_digests() {
+        let mut subject = Map::new();
+        subject.insert("name".into(), json!("Alice"));
+        let disclosures = redact_claims(&mut subject, &["name".to_string()]);
+        let jwt = jwt_with_sd(&[subject["_sd"][0].as_str().unwrap().to_string()]);
+
+        let encoded = disclosures[0].encoded.as_str();
+        assert!(verify_disclosures(&jwt, &[encoded]).is_ok());
+    }
+
+    #[test]
+    fn verify_disclosures_reconstructs_revealed_claims_and_elements() {
+        let mut subject = Map::new();
+        subject.insert("name".into(), json!("Alice"));
+        subject.insert("achievements".into(), json!(["scuba", "skydiving"]));
+        let disclosures = redact_claims(
+            &mut subject,
+            &["name".to_string(), "achievements[1]".to_string()],
+        );
+        let payload = json!({ "credentialSubject": subject });
+        let header = URL_SAFE_NO_PAD.encode(b"{}");
+        let payload_b64 = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload).unwrap());
+        let jwt = format!("{header}.{payload_b64}.sig");
+
+        let encoded: Vec<&str> = disclosures.iter().map(|d| d.encoded.as_str()).collect();
+        let revealed = verify_disclosures(&jwt, &encoded).unwrap();
+
+        let revealed_subject = &revealed["credentialSubject"];
+        assert_eq!(revealed_subject["name"], json!("Alice"));
+        assert_eq!(
+            revealed_subject["achievements"],
+            json!(["scuba", "skydiving"])
+        );
+        assert!(!revealed_subject.as_object().unwrap().contains_key("_sd"));
+    }
+
+    #[test]
+    fn verify_disclosures_rejects_unknown_digest() {
+        let jwt = jwt_with_sd(&[]);
+        let bogus = encode_array(&[
+            Value::String(random_salt()),
+            Value::String("name".into()),
+            json!("Eve"),
+        ]);
+        assert!(verify_disclosures(&jwt, &[bogus.as_str()]).is_err());
+    }
+
+    #[test]
+    fn select_disclosures_drops_unselected_claims() {
+        let mut subject = Map::new();
+        subject.insert("name".into(), json!("Alice"));
+        subject.insert("email".into(), json!("alice@example.com"));
+        let disclosures = redact_claims(&mut subject, &["name".to_string(), "email".to_string()]);
+        let combined = format!(
+            "header.payload.sig~{}~{}~",
+            disclosures[0].encoded, disclosures[1].encoded
+        );
+
+        let selected = select_disclosures(&combined, &["name".to_string()]).unwrap();
+
+        assert!(selected.contains(&disclosures[0].encoded));
+        assert!(!selected.contains(&disclosures[1].encoded));
+    }
+}